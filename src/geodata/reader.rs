@@ -0,0 +1,25 @@
+use std::collections::HashMap;
+
+pub struct Tags<'e> {
+    map: &'e HashMap<String, String>,
+}
+
+impl<'e> Tags<'e> {
+    pub fn new(map: &'e HashMap<String, String>) -> Tags<'e> {
+        Tags { map }
+    }
+
+    pub fn get_by_key(&self, key: &str) -> Option<&'e String> {
+        self.map.get(key)
+    }
+}
+
+pub trait OsmEntity<'e> {
+    fn global_id(&self) -> u64;
+    fn tags(&self) -> Tags<'e>;
+}
+
+pub trait OsmArea {
+    fn is_closed(&self) -> bool;
+    fn parents(&self) -> Vec<&Self>;
+}
@@ -0,0 +1,44 @@
+use serde_derive::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub fn to_rgba_f32(&self) -> [f32; 4] {
+        [
+            f32::from(self.r) / 255.0,
+            f32::from(self.g) / 255.0,
+            f32::from(self.b) / 255.0,
+            1.0,
+        ]
+    }
+}
+
+pub fn from_color_name(name: &str) -> Option<Color> {
+    match name {
+        "black" => Some(Color { r: 0, g: 0, b: 0 }),
+        "white" => Some(Color {
+            r: 255,
+            g: 255,
+            b: 255,
+        }),
+        "red" => Some(Color { r: 255, g: 0, b: 0 }),
+        "green" => Some(Color { r: 0, g: 128, b: 0 }),
+        "blue" => Some(Color { r: 0, g: 0, b: 255 }),
+        "yellow" => Some(Color {
+            r: 255,
+            g: 255,
+            b: 0,
+        }),
+        "gray" | "grey" => Some(Color {
+            r: 128,
+            g: 128,
+            b: 128,
+        }),
+        _ => None,
+    }
+}
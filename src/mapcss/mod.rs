@@ -0,0 +1,3 @@
+pub mod color;
+pub mod parser;
+pub mod styler;
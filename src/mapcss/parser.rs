@@ -0,0 +1,88 @@
+use serde_derive::{Deserialize, Serialize};
+
+use mapcss::color::Color;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Rule {
+    pub selectors: Vec<Selector>,
+    pub properties: Vec<Property>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Selector {
+    Single(SingleSelector),
+    Nested {
+        parent: SingleSelector,
+        child: SingleSelector,
+    },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SingleSelector {
+    pub object_type: ObjectType,
+    pub tests: Vec<Test>,
+    pub layer_id: Option<String>,
+    pub min_zoom: Option<u8>,
+    pub max_zoom: Option<u8>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum ObjectType {
+    Canvas,
+    Node,
+    Way { should_be_closed: Option<bool> },
+    Relation,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Test {
+    Unary {
+        tag_name: String,
+        test_type: UnaryTestType,
+    },
+    BinaryStringCompare {
+        tag_name: String,
+        value: String,
+        test_type: BinaryStringTestType,
+    },
+    BinaryNumericCompare {
+        tag_name: String,
+        value: f64,
+        test_type: BinaryNumericTestType,
+    },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum UnaryTestType {
+    Exists,
+    NotExists,
+    True,
+    False,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum BinaryStringTestType {
+    Equal,
+    NotEqual,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum BinaryNumericTestType {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Property {
+    pub name: String,
+    pub value: PropertyValue,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum PropertyValue {
+    Color(Color),
+    Identifier(String),
+    Numbers(Vec<f64>),
+}
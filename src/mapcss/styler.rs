@@ -2,16 +2,23 @@ use mapcss::color::{from_color_name, Color};
 use mapcss::parser::*;
 
 use geodata::reader::{OsmArea, OsmEntity};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
 
-#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub enum LineJoin {
     Round,
     Miter,
     Bevel,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub enum LineCap {
     Butt,
     Round,
@@ -25,7 +32,7 @@ pub fn is_non_trivial_cap(line_cap: &Option<LineCap>) -> bool {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Style {
     pub z_index: f64,
 
@@ -77,6 +84,24 @@ pub struct Styler {
     rules: Vec<Rule>,
 }
 
+/// Bumped whenever `CachedStyler`'s shape changes, so a cache file written by an older binary is
+/// rejected instead of being (mis)deserialized.
+const CACHE_FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct CachedStyler {
+    format_version: u32,
+    source_hash: u64,
+    rules: Vec<Rule>,
+    canvas_fill_color: Option<Color>,
+}
+
+fn hash_source(mapcss_source: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    mapcss_source.hash(&mut hasher);
+    hasher.finish()
+}
+
 impl Styler {
     pub fn new(rules: Vec<Rule>) -> Styler {
         let canvas_fill_color = extract_canvas_fill_color(&rules);
@@ -87,6 +112,41 @@ impl Styler {
         }
     }
 
+    /// Loads a previously cached, already-parsed styler from `path`, provided its format version
+    /// and source hash still match `mapcss_source`. Returns `None` on any mismatch or I/O/decode
+    /// error, in which case the caller should fall back to parsing `mapcss_source` from scratch.
+    pub fn from_cache<P: AsRef<Path>>(path: P, mapcss_source: &[u8]) -> Option<Styler> {
+        let file = File::open(path).ok()?;
+        let cached: CachedStyler = serde_cbor::from_reader(BufReader::new(file)).ok()?;
+
+        if cached.format_version != CACHE_FORMAT_VERSION {
+            return None;
+        }
+        if cached.source_hash != hash_source(mapcss_source) {
+            return None;
+        }
+
+        Some(Styler {
+            rules: cached.rules,
+            canvas_fill_color: cached.canvas_fill_color,
+        })
+    }
+
+    /// Writes this styler's rules to `path` as CBOR, tagged with `mapcss_source`'s hash so a
+    /// later `from_cache` call can detect a stale cache if the source has since changed.
+    pub fn write_cache<P: AsRef<Path>>(&self, path: P, mapcss_source: &[u8]) -> io::Result<()> {
+        let cached = CachedStyler {
+            format_version: CACHE_FORMAT_VERSION,
+            source_hash: hash_source(mapcss_source),
+            rules: self.rules.clone(),
+            canvas_fill_color: self.canvas_fill_color.clone(),
+        };
+
+        let file = File::create(path)?;
+        serde_cbor::to_writer(BufWriter::new(file), &cached)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+    }
+
     pub fn style_areas<'e, 'wp, I, A>(&self, areas: I, zoom: u8) -> Vec<(&'wp A, Style)>
     where
         A: OsmArea + OsmEntity<'e>,
@@ -95,10 +155,11 @@ impl Styler {
         let mut styled_areas = areas
             .flat_map(|x| {
                 let default_z_index = if x.is_closed() { 1.0 } else { 3.0 };
-                self.style_area(x, zoom)
+                let parents = x.parents();
+                self.style_area(x, &parents, zoom)
                     .into_iter()
                     .filter(|&(k, _)| k != "*")
-                    .map(move |(_, v)| (x, property_map_to_style(&v, default_z_index, x)))
+                    .map(move |(_, v)| (x, property_map_to_style(&v, default_z_index, x, zoom)))
             })
             .collect::<Vec<_>>();
 
@@ -111,7 +172,7 @@ impl Styler {
         styled_areas
     }
 
-    fn style_area<'r, 'e, A>(&'r self, area: &A, zoom: u8) -> LayerToPropertyMap<'r>
+    fn style_area<'r, 'e, A>(&'r self, area: &A, parents: &[&A], zoom: u8) -> LayerToPropertyMap<'r>
     where
         A: OsmArea + OsmEntity<'e>,
     {
@@ -120,7 +181,7 @@ impl Styler {
         for rule in &self.rules {
             for sel in rule.selectors
                 .iter()
-                .filter(|x| area_matches(area, x, zoom))
+                .filter(|x| area_matches(area, parents, x, zoom))
             {
                 let layer_id = get_layer_id(sel);
 
@@ -161,6 +222,7 @@ fn property_map_to_style<'r, 'e, E>(
     property_map: &PropertyMap<'r>,
     default_z_index: f64,
     osm_entity: &E,
+    zoom: u8,
 ) -> Style
 where
     E: OsmEntity<'e>,
@@ -192,8 +254,19 @@ where
         }
     };
 
+    // A single number is a flat value. An even-length list of 4 or more numbers is read as
+    // `zoom, value` stops (e.g. `width: 12,1,18,6;` means width 1 at zoom 12, ramping linearly
+    // to width 6 at zoom 18, clamped to the end values outside that range).
     let get_num = |prop_name| match property_map.get(prop_name) {
         Some(&&PropertyValue::Numbers(ref nums)) if nums.len() == 1 => Some(nums[0]),
+        Some(&&PropertyValue::Numbers(ref nums)) if nums.len() >= 4 && nums.len() % 2 == 0 => {
+            let mut stops = nums
+                .chunks(2)
+                .map(|stop| (stop[0] as u8, stop[1]))
+                .collect::<Vec<_>>();
+            stops.sort_by_key(|&(zoom, _)| zoom);
+            Some(interpolate_num(&stops, zoom))
+        }
         _ => {
             warn(prop_name, "expected a number");
             None
@@ -356,14 +429,47 @@ where
     good_object_type && selector.tests.iter().all(|x| matches_by_tags(area, x))
 }
 
-fn area_matches<'e, A>(area: &A, selector: &Selector, zoom: u8) -> bool
+fn area_matches<'e, A>(area: &A, parents: &[&A], selector: &Selector, zoom: u8) -> bool
 where
     A: OsmArea + OsmEntity<'e>,
 {
     match *selector {
-        Selector::Nested { .. } => false,
         Selector::Single(ref sel) => area_matches_single(area, sel, zoom),
+        Selector::Nested {
+            ref parent,
+            ref child,
+        } => {
+            area_matches_single(area, child, zoom)
+                && parents.iter().any(|&p| parent_matches(p, parent, zoom))
+        }
+    }
+}
+
+// Like area_matches_single, but used to test a candidate ancestor against the parent half of a
+// nested selector: relations are valid parents even though they can never match a selector as
+// the object being styled, and closedness of the parent way is irrelevant to the match.
+fn parent_matches<'e, A>(parent: &A, selector: &SingleSelector, zoom: u8) -> bool
+where
+    A: OsmArea + OsmEntity<'e>,
+{
+    if let Some(min_zoom) = selector.min_zoom {
+        if zoom < min_zoom {
+            return false;
+        }
     }
+
+    if let Some(max_zoom) = selector.max_zoom {
+        if zoom > max_zoom {
+            return false;
+        }
+    }
+
+    let good_object_type = match selector.object_type {
+        ObjectType::Way { .. } | ObjectType::Relation => true,
+        _ => return false,
+    };
+
+    good_object_type && selector.tests.iter().all(|x| matches_by_tags(parent, x))
 }
 
 fn get_layer_id(selector: &Selector) -> &str {
@@ -376,3 +482,270 @@ fn get_layer_id(selector: &Selector) -> &str {
         None => "default",
     }
 }
+
+/// Linearly interpolates between the `(zoom, value)` stops for `zoom`, clamping to the first or
+/// last stop's value when `zoom` falls outside their range. `stops` must be sorted by zoom and
+/// non-empty.
+fn interpolate_num(stops: &[(u8, f64)], zoom: u8) -> f64 {
+    if zoom <= stops[0].0 {
+        return stops[0].1;
+    }
+
+    if zoom >= stops[stops.len() - 1].0 {
+        return stops[stops.len() - 1].1;
+    }
+
+    for window in stops.windows(2) {
+        let (z1, v1) = window[0];
+        let (z2, v2) = window[1];
+        if zoom >= z1 && zoom <= z2 {
+            if z1 == z2 {
+                return v1;
+            }
+            let t = f64::from(zoom - z1) / f64::from(z2 - z1);
+            return v1 + (v2 - v1) * t;
+        }
+    }
+
+    stops[stops.len() - 1].1
+}
+
+#[cfg(test)]
+mod interpolate_num_tests {
+    use super::interpolate_num;
+
+    #[test]
+    fn clamps_below_first_stop() {
+        let stops = [(12, 1.0), (18, 6.0)];
+        assert_eq!(interpolate_num(&stops, 5), 1.0);
+    }
+
+    #[test]
+    fn clamps_above_last_stop() {
+        let stops = [(12, 1.0), (18, 6.0)];
+        assert_eq!(interpolate_num(&stops, 20), 6.0);
+    }
+
+    #[test]
+    fn interpolates_linearly_between_two_stops() {
+        let stops = [(12, 1.0), (18, 6.0)];
+        assert_eq!(interpolate_num(&stops, 15), 3.5);
+    }
+
+    #[test]
+    fn interpolates_across_multiple_stops() {
+        let stops = [(10, 0.0), (14, 4.0), (20, 10.0)];
+        assert_eq!(interpolate_num(&stops, 12), 2.0);
+        assert_eq!(interpolate_num(&stops, 17), 7.0);
+    }
+
+    #[test]
+    fn exact_stop_returns_its_value() {
+        let stops = [(12, 1.0), (18, 6.0)];
+        assert_eq!(interpolate_num(&stops, 18), 6.0);
+    }
+}
+
+#[cfg(test)]
+mod cache_tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    fn empty_styler() -> Styler {
+        Styler::new(vec![Rule {
+            selectors: vec![Selector::Single(SingleSelector {
+                object_type: ObjectType::Node,
+                tests: vec![],
+                layer_id: None,
+                min_zoom: None,
+                max_zoom: None,
+            })],
+            properties: vec![Property {
+                name: "color".to_string(),
+                value: PropertyValue::Identifier("red".to_string()),
+            }],
+        }])
+    }
+
+    fn cache_path(name: &str) -> std::path::PathBuf {
+        let mut path = env::temp_dir();
+        path.push(format!("osm_renderer_styler_cache_test_{}", name));
+        path
+    }
+
+    #[test]
+    fn cache_round_trips_when_source_is_unchanged() {
+        let path = cache_path("round_trip");
+        let source = b"node { color: red; }";
+
+        let styler = empty_styler();
+        styler.write_cache(&path, source).unwrap();
+
+        let loaded = Styler::from_cache(&path, source).expect("cache should load");
+        assert_eq!(loaded.rules.len(), styler.rules.len());
+        assert_eq!(loaded.canvas_fill_color, styler.canvas_fill_color);
+
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn cache_is_rejected_when_source_hash_changes() {
+        let path = cache_path("hash_mismatch");
+        let original_source = b"node { color: red; }";
+        let changed_source = b"node { color: blue; }";
+
+        let styler = empty_styler();
+        styler.write_cache(&path, original_source).unwrap();
+
+        assert!(Styler::from_cache(&path, changed_source).is_none());
+
+        fs::remove_file(&path).ok();
+    }
+}
+
+#[cfg(test)]
+mod nested_selector_tests {
+    use super::*;
+    use geodata::reader::Tags;
+
+    struct MockEntity<'e> {
+        id: u64,
+        closed: bool,
+        tags: &'e HashMap<String, String>,
+        parent_entities: Vec<MockEntity<'e>>,
+    }
+
+    impl<'e> OsmEntity<'e> for MockEntity<'e> {
+        fn global_id(&self) -> u64 {
+            self.id
+        }
+
+        fn tags(&self) -> Tags<'e> {
+            Tags::new(self.tags)
+        }
+    }
+
+    impl<'e> OsmArea for MockEntity<'e> {
+        fn is_closed(&self) -> bool {
+            self.closed
+        }
+
+        fn parents(&self) -> Vec<&MockEntity<'e>> {
+            self.parent_entities.iter().collect()
+        }
+    }
+
+    fn tag_equals(tag_name: &str, value: &str) -> Test {
+        Test::BinaryStringCompare {
+            tag_name: tag_name.to_string(),
+            value: value.to_string(),
+            test_type: BinaryStringTestType::Equal,
+        }
+    }
+
+    fn single_selector(object_type: ObjectType, tests: Vec<Test>) -> SingleSelector {
+        SingleSelector {
+            object_type,
+            tests,
+            layer_id: None,
+            min_zoom: None,
+            max_zoom: None,
+        }
+    }
+
+    fn multipolygon_relation_selector() -> SingleSelector {
+        single_selector(
+            ObjectType::Relation,
+            vec![tag_equals("type", "multipolygon")],
+        )
+    }
+
+    #[test]
+    fn nested_selector_matches_way_with_matching_parent() {
+        let empty_tags = HashMap::new();
+        let mut parent_tags = HashMap::new();
+        parent_tags.insert("type".to_string(), "multipolygon".to_string());
+
+        let parent = MockEntity {
+            id: 1,
+            closed: true,
+            tags: &parent_tags,
+            parent_entities: vec![],
+        };
+        let child = MockEntity {
+            id: 2,
+            closed: true,
+            tags: &empty_tags,
+            parent_entities: vec![],
+        };
+
+        let selector = Selector::Nested {
+            parent: multipolygon_relation_selector(),
+            child: single_selector(ObjectType::Way { should_be_closed: None }, vec![]),
+        };
+
+        assert!(area_matches(&child, &[&parent], &selector, 10));
+    }
+
+    #[test]
+    fn nested_selector_rejects_way_without_matching_parent() {
+        let empty_tags = HashMap::new();
+        let mut other_tags = HashMap::new();
+        other_tags.insert("type".to_string(), "boundary".to_string());
+
+        let parent = MockEntity {
+            id: 1,
+            closed: true,
+            tags: &other_tags,
+            parent_entities: vec![],
+        };
+        let child = MockEntity {
+            id: 2,
+            closed: true,
+            tags: &empty_tags,
+            parent_entities: vec![],
+        };
+
+        let selector = Selector::Nested {
+            parent: multipolygon_relation_selector(),
+            child: single_selector(ObjectType::Way { should_be_closed: None }, vec![]),
+        };
+
+        assert!(!area_matches(&child, &[&parent], &selector, 10));
+    }
+
+    #[test]
+    fn parent_matches_respects_zoom_bounds() {
+        let mut tags = HashMap::new();
+        tags.insert("type".to_string(), "multipolygon".to_string());
+        let parent = MockEntity {
+            id: 1,
+            closed: true,
+            tags: &tags,
+            parent_entities: vec![],
+        };
+
+        let mut selector = multipolygon_relation_selector();
+        selector.min_zoom = Some(12);
+        selector.max_zoom = Some(16);
+
+        assert!(!parent_matches(&parent, &selector, 10));
+        assert!(parent_matches(&parent, &selector, 14));
+        assert!(!parent_matches(&parent, &selector, 18));
+    }
+
+    #[test]
+    fn parent_matches_accepts_relations_as_parents() {
+        let mut tags = HashMap::new();
+        tags.insert("type".to_string(), "multipolygon".to_string());
+        let parent = MockEntity {
+            id: 1,
+            closed: false,
+            tags: &tags,
+            parent_entities: vec![],
+        };
+
+        assert!(parent_matches(&parent, &multipolygon_relation_selector(), 10));
+    }
+}
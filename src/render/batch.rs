@@ -0,0 +1,31 @@
+use std::collections::HashMap;
+
+use mapcss::styler::{Style, StyleHashKey};
+
+/// A run of areas that share an identical `Style`, so they can be drawn with a single GPU
+/// instance buffer instead of one draw call per area.
+pub struct StyleGroup<'a, A: 'a> {
+    pub style: Style,
+    pub entities: Vec<&'a A>,
+}
+
+/// Groups styled areas by their `StyleHashKey`, preserving the z-order the areas already came
+/// in (the first area to use a given style determines where that style's group sits).
+pub fn group_by_style<'a, A>(styled_areas: Vec<(&'a A, Style)>) -> Vec<StyleGroup<'a, A>> {
+    let mut group_index: HashMap<StyleHashKey, usize> = HashMap::new();
+    let mut groups: Vec<StyleGroup<'a, A>> = Vec::new();
+
+    for (area, style) in styled_areas {
+        let key = style.to_hash_key();
+        let idx = *group_index.entry(key).or_insert_with(|| {
+            groups.push(StyleGroup {
+                style,
+                entities: Vec::new(),
+            });
+            groups.len() - 1
+        });
+        groups[idx].entities.push(area);
+    }
+
+    groups
+}
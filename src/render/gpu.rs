@@ -0,0 +1,269 @@
+use wgpu::util::DeviceExt;
+
+use mapcss::styler::Style;
+use render::batch::StyleGroup;
+use render::stroke::{tessellate_stroke, StrokeGeometry};
+
+pub const MAX_DASH_STOPS: usize = 8;
+
+/// Per-instance data mirrored by the `StyleInstance` struct in fill.wgsl/stroke.wgsl.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct StyleInstance {
+    pub color: [f32; 4],
+    pub width: f32,
+    pub dash_stop_count: u32,
+    pub dash_stops: [f32; MAX_DASH_STOPS],
+    /// Pads the struct to 64 bytes, the size WGSL gives it (its `vec4` makes it 16-byte aligned).
+    pub _padding: [f32; 2],
+}
+
+pub struct BatchRenderer {
+    fill_pipeline: wgpu::RenderPipeline,
+    stroke_pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+/// One style group's worth of GPU resources, fully prepared ahead of the render pass: the
+/// vertex/(optional index) buffers and the bind group (through which the instance buffer is
+/// bound) all need to outlive the `RenderPass` they're bound into, so they're collected here and
+/// kept alive in a `Vec` declared before the pass is opened, rather than created from inside a
+/// function the pass borrows into.
+struct DrawCall<'p> {
+    pipeline: &'p wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    vertex_buffer: wgpu::Buffer,
+    vertex_count: u32,
+    index_buffer: Option<wgpu::Buffer>,
+    index_count: u32,
+}
+
+impl BatchRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        color_format: wgpu::TextureFormat,
+        fill_shader: &wgpu::ShaderModule,
+        stroke_shader: &wgpu::ShaderModule,
+    ) -> BatchRenderer {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("style_instance_bind_group_layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("batch_renderer_pipeline_layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let vertex_buffer_layout = wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32],
+        };
+
+        let make_pipeline = |shader: &wgpu::ShaderModule, label: &str| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: shader,
+                    entry_point: "vs_main",
+                    buffers: &[vertex_buffer_layout.clone()],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: color_format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+        };
+
+        BatchRenderer {
+            fill_pipeline: make_pipeline(fill_shader, "fill_pipeline"),
+            stroke_pipeline: make_pipeline(stroke_shader, "stroke_pipeline"),
+            bind_group_layout,
+        }
+    }
+
+    fn make_instance(style: &Style) -> StyleInstance {
+        let color = style
+            .fill_color
+            .as_ref()
+            .or(style.color.as_ref())
+            .map(|c| c.to_rgba_f32())
+            .unwrap_or([0.0, 0.0, 0.0, 1.0]);
+
+        let mut dash_stops = [0.0; MAX_DASH_STOPS];
+        let dash_stop_count = match style.dashes {
+            Some(ref dashes) => {
+                let n = dashes.len().min(MAX_DASH_STOPS);
+                dash_stops[..n].copy_from_slice(&dashes[..n].iter().map(|x| *x as f32).collect::<Vec<_>>());
+                n as u32
+            }
+            None => 0,
+        };
+
+        StyleInstance {
+            color,
+            width: style.width.unwrap_or(1.0) as f32,
+            dash_stop_count,
+            dash_stops,
+            _padding: [0.0; 2],
+        }
+    }
+
+    fn prepare_fill_draw<'p>(
+        &'p self,
+        device: &wgpu::Device,
+        vertices: &[[f32; 3]],
+        style: &Style,
+    ) -> DrawCall<'p> {
+        let instance = Self::make_instance(style);
+
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("fill_vertex_buffer"),
+            contents: bytemuck::cast_slice(vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("fill_instance_buffer"),
+            contents: bytemuck::cast_slice(&[instance]),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("fill_bind_group"),
+            layout: &self.bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: instance_buffer.as_entire_binding(),
+            }],
+        });
+
+        DrawCall {
+            pipeline: &self.fill_pipeline,
+            bind_group,
+            vertex_buffer,
+            vertex_count: vertices.len() as u32,
+            index_buffer: None,
+            index_count: 0,
+        }
+    }
+
+    fn prepare_stroke_draw<'p>(
+        &'p self,
+        device: &wgpu::Device,
+        geometry: &StrokeGeometry,
+        style: &Style,
+    ) -> DrawCall<'p> {
+        let instance = Self::make_instance(style);
+
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("stroke_vertex_buffer"),
+            contents: bytemuck::cast_slice(&geometry.vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("stroke_index_buffer"),
+            contents: bytemuck::cast_slice(&geometry.indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("stroke_instance_buffer"),
+            contents: bytemuck::cast_slice(&[instance]),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("stroke_bind_group"),
+            layout: &self.bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: instance_buffer.as_entire_binding(),
+            }],
+        });
+
+        DrawCall {
+            pipeline: &self.stroke_pipeline,
+            bind_group,
+            vertex_buffer,
+            vertex_count: geometry.vertices.len() as u32,
+            index_buffer: Some(index_buffer),
+            index_count: geometry.indices.len() as u32,
+        }
+    }
+
+    /// Draws every group in `fill_groups` followed by every group in `stroke_groups`, keyed by
+    /// style so that areas sharing an identical `Style` end up in the same instance buffer.
+    pub fn render_groups<'a, A>(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        fill_groups: &[(StyleGroup<'a, A>, Vec<[f32; 3]>)],
+        stroke_groups: &[(StyleGroup<'a, A>, Vec<(f64, f64)>)],
+    ) {
+        // Declared before the render pass so that, per Rust's reverse-declaration-order drop
+        // semantics, every buffer and bind group here outlives the pass borrowing into it.
+        let mut draw_calls: Vec<DrawCall> = Vec::new();
+
+        for (group, vertices) in fill_groups {
+            draw_calls.push(self.prepare_fill_draw(device, vertices, &group.style));
+        }
+
+        for (group, points) in stroke_groups {
+            let geometry = tessellate_stroke(points, &group.style);
+            draw_calls.push(self.prepare_stroke_draw(device, &geometry, &group.style));
+        }
+
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("batch_renderer_pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+
+        for call in &draw_calls {
+            pass.set_pipeline(call.pipeline);
+            pass.set_bind_group(0, &call.bind_group, &[]);
+            pass.set_vertex_buffer(0, call.vertex_buffer.slice(..));
+
+            match call.index_buffer {
+                Some(ref index_buffer) => {
+                    pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                    pass.draw_indexed(0..call.index_count, 0, 0..1);
+                }
+                None => {
+                    pass.draw(0..call.vertex_count, 0..1);
+                }
+            }
+        }
+    }
+}
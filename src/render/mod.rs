@@ -0,0 +1,7 @@
+pub mod batch;
+pub mod gpu;
+pub mod stroke;
+
+pub use self::batch::{group_by_style, StyleGroup};
+pub use self::gpu::BatchRenderer;
+pub use self::stroke::{tessellate_stroke, StrokeGeometry, StrokeVertex};
@@ -0,0 +1,321 @@
+use std::f64::consts::PI;
+
+use mapcss::styler::{LineCap, LineJoin, Style};
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct StrokeVertex {
+    pub x: f32,
+    pub y: f32,
+    /// Distance travelled along the stroke's centerline, used by the fragment shader to decide
+    /// whether a given point falls in an "on" or "off" dash segment.
+    pub dist: f32,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct StrokeGeometry {
+    pub vertices: Vec<StrokeVertex>,
+    pub indices: Vec<u32>,
+}
+
+impl StrokeGeometry {
+    fn push_quad(&mut self, a: StrokeVertex, b: StrokeVertex, c: StrokeVertex, d: StrokeVertex) {
+        let base = self.vertices.len() as u32;
+        self.vertices.push(a);
+        self.vertices.push(b);
+        self.vertices.push(c);
+        self.vertices.push(d);
+        self.indices
+            .extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+    }
+
+    fn push_triangle(&mut self, a: StrokeVertex, b: StrokeVertex, c: StrokeVertex) {
+        let base = self.vertices.len() as u32;
+        self.vertices.push(a);
+        self.vertices.push(b);
+        self.vertices.push(c);
+        self.indices.extend_from_slice(&[base, base + 1, base + 2]);
+    }
+}
+
+fn normal(dx: f64, dy: f64) -> (f64, f64) {
+    let len = (dx * dx + dy * dy).sqrt();
+    if len == 0.0 {
+        (0.0, 0.0)
+    } else {
+        (-dy / len, dx / len)
+    }
+}
+
+/// Tessellates a polyline into a triangle strip (as indexed triangles) `style.width` units wide,
+/// with joins and caps matching `style.line_join`/`style.line_cap`.
+pub fn tessellate_stroke(points: &[(f64, f64)], style: &Style) -> StrokeGeometry {
+    let mut geometry = StrokeGeometry::default();
+
+    if points.len() < 2 {
+        return geometry;
+    }
+
+    let half_width = style.width.unwrap_or(1.0) / 2.0;
+    let line_join = style.line_join.clone().unwrap_or(LineJoin::Miter);
+    let line_cap = style.line_cap.clone().unwrap_or(LineCap::Butt);
+
+    let mut dist = 0.0_f64;
+
+    for i in 0..points.len() - 1 {
+        let (x1, y1) = points[i];
+        let (x2, y2) = points[i + 1];
+        let (dx, dy) = (x2 - x1, y2 - y1);
+        let seg_len = (dx * dx + dy * dy).sqrt();
+        let (nx, ny) = normal(dx, dy);
+
+        let v = |x: f64, y: f64, d: f64| StrokeVertex {
+            x: x as f32,
+            y: y as f32,
+            dist: d as f32,
+        };
+
+        geometry.push_quad(
+            v(x1 + nx * half_width, y1 + ny * half_width, dist),
+            v(x1 - nx * half_width, y1 - ny * half_width, dist),
+            v(x2 - nx * half_width, y2 - ny * half_width, dist + seg_len),
+            v(x2 + nx * half_width, y2 + ny * half_width, dist + seg_len),
+        );
+
+        if i + 2 < points.len() {
+            let (x3, y3) = points[i + 2];
+            tessellate_join(
+                &mut geometry,
+                (x2, y2),
+                (dx, dy),
+                (x3 - x2, y3 - y2),
+                half_width,
+                dist + seg_len,
+                &line_join,
+            );
+        }
+
+        dist += seg_len;
+    }
+
+    let (sx, sy) = points[0];
+    let (sdx, sdy) = (points[0].0 - points[1].0, points[0].1 - points[1].1);
+    tessellate_cap(&mut geometry, (sx, sy), (sdx, sdy), half_width, 0.0, &line_cap);
+
+    let (ex, ey) = points[points.len() - 1];
+    let (edx, edy) = (
+        points[points.len() - 1].0 - points[points.len() - 2].0,
+        points[points.len() - 1].1 - points[points.len() - 2].1,
+    );
+    tessellate_cap(&mut geometry, (ex, ey), (edx, edy), half_width, dist, &line_cap);
+
+    geometry
+}
+
+const JOIN_SEGMENTS: usize = 6;
+
+fn tessellate_join(
+    geometry: &mut StrokeGeometry,
+    center: (f64, f64),
+    dir_in: (f64, f64),
+    dir_out: (f64, f64),
+    half_width: f64,
+    dist: f64,
+    line_join: &LineJoin,
+) {
+    let (cx, cy) = center;
+    let (n_in_x, n_in_y) = normal(dir_in.0, dir_in.1);
+    let (n_out_x, n_out_y) = normal(dir_out.0, dir_out.1);
+
+    // Cross product sign tells us which side of the joint is the outer (convex) corner; only
+    // that side needs filler geometry, the inner side is already covered by the two segments.
+    let cross = dir_in.0 * dir_out.1 - dir_in.1 * dir_out.0;
+    let side = if cross < 0.0 { 1.0 } else { -1.0 };
+
+    // A straight continuation has no outer corner, so it gets no join geometry at all.
+    if cross == 0.0 && dir_in.0 * dir_out.0 + dir_in.1 * dir_out.1 >= 0.0 {
+        return;
+    }
+
+    let center_v = StrokeVertex {
+        x: cx as f32,
+        y: cy as f32,
+        dist: dist as f32,
+    };
+    let in_v = StrokeVertex {
+        x: (cx + side * n_in_x * half_width) as f32,
+        y: (cy + side * n_in_y * half_width) as f32,
+        dist: dist as f32,
+    };
+    let out_v = StrokeVertex {
+        x: (cx + side * n_out_x * half_width) as f32,
+        y: (cy + side * n_out_y * half_width) as f32,
+        dist: dist as f32,
+    };
+
+    match *line_join {
+        LineJoin::Bevel => {
+            geometry.push_triangle(center_v, in_v, out_v);
+        }
+        LineJoin::Miter => {
+            let mid_x = n_in_x + n_out_x;
+            let mid_y = n_in_y + n_out_y;
+            let mid_len = (mid_x * mid_x + mid_y * mid_y).sqrt();
+            let cos_half_angle = if mid_len == 0.0 { 0.0 } else { mid_len / 2.0 };
+
+            // Near-180-degree turns blow the miter length up towards infinity; fall back to a
+            // bevel rather than emitting a degenerate/huge spike.
+            if cos_half_angle < 0.1 {
+                geometry.push_triangle(center_v, in_v, out_v);
+            } else {
+                let miter_len = half_width / cos_half_angle;
+                let miter_v = StrokeVertex {
+                    x: (cx + side * (mid_x / mid_len) * miter_len) as f32,
+                    y: (cy + side * (mid_y / mid_len) * miter_len) as f32,
+                    dist: dist as f32,
+                };
+                geometry.push_triangle(center_v, in_v, miter_v);
+                geometry.push_triangle(center_v, miter_v, out_v);
+            }
+        }
+        LineJoin::Round => {
+            let start_angle = n_in_y.atan2(n_in_x);
+            let mut end_angle = n_out_y.atan2(n_out_x);
+
+            if side > 0.0 {
+                while end_angle > start_angle {
+                    end_angle -= 2.0 * PI;
+                }
+            } else {
+                while end_angle < start_angle {
+                    end_angle += 2.0 * PI;
+                }
+            }
+
+            let mut prev = in_v;
+            for step in 1..=JOIN_SEGMENTS {
+                let t = start_angle
+                    + (end_angle - start_angle) * (step as f64) / (JOIN_SEGMENTS as f64);
+                let next = StrokeVertex {
+                    x: (cx + side * t.cos() * half_width) as f32,
+                    y: (cy + side * t.sin() * half_width) as f32,
+                    dist: dist as f32,
+                };
+                geometry.push_triangle(center_v, prev, next);
+                prev = next;
+            }
+        }
+    }
+}
+
+const CAP_SEGMENTS: usize = 8;
+
+fn tessellate_cap(
+    geometry: &mut StrokeGeometry,
+    center: (f64, f64),
+    dir_outward: (f64, f64),
+    half_width: f64,
+    dist: f64,
+    line_cap: &LineCap,
+) {
+    if let LineCap::Butt = *line_cap {
+        return;
+    }
+
+    let (cx, cy) = center;
+    let (dx, dy) = dir_outward;
+    let len = (dx * dx + dy * dy).sqrt();
+    if len == 0.0 {
+        return;
+    }
+    let (fx, fy) = (dx / len, dy / len);
+    let (nx, ny) = normal(dx, dy);
+
+    let v = |x: f64, y: f64| StrokeVertex {
+        x: x as f32,
+        y: y as f32,
+        dist: dist as f32,
+    };
+
+    let left = (cx + nx * half_width, cy + ny * half_width);
+    let right = (cx - nx * half_width, cy - ny * half_width);
+
+    match *line_cap {
+        LineCap::Butt => {}
+        LineCap::Square => {
+            let far_left = (left.0 + fx * half_width, left.1 + fy * half_width);
+            let far_right = (right.0 + fx * half_width, right.1 + fy * half_width);
+            geometry.push_quad(
+                v(left.0, left.1),
+                v(right.0, right.1),
+                v(far_right.0, far_right.1),
+                v(far_left.0, far_left.1),
+            );
+        }
+        LineCap::Round => {
+            let start_angle = ny.atan2(nx);
+            let center_v = v(cx, cy);
+            let mut prev = v(left.0, left.1);
+            for step in 1..=CAP_SEGMENTS {
+                let t = start_angle - PI * (step as f64) / (CAP_SEGMENTS as f64);
+                let next = v(cx + t.cos() * half_width, cy + t.sin() * half_width);
+                geometry.push_triangle(center_v, prev, next);
+                prev = next;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn style_with(line_join: LineJoin, line_cap: LineCap) -> Style {
+        Style {
+            z_index: 0.0,
+            color: None,
+            fill_color: None,
+            opacity: None,
+            fill_opacity: None,
+            width: Some(2.0),
+            dashes: None,
+            line_join: Some(line_join),
+            line_cap: Some(line_cap),
+        }
+    }
+
+    #[test]
+    fn straight_line_has_no_join_geometry() {
+        let style = style_with(LineJoin::Round, LineCap::Butt);
+        let points = [(0.0, 0.0), (1.0, 0.0), (2.0, 0.0)];
+        let geometry = tessellate_stroke(&points, &style);
+        // Two straight segments, no joins or caps: exactly two quads.
+        assert_eq!(geometry.vertices.len(), 8);
+        assert_eq!(geometry.indices.len(), 12);
+    }
+
+    #[test]
+    fn bevel_join_adds_a_single_triangle() {
+        let style = style_with(LineJoin::Bevel, LineCap::Butt);
+        let points = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)];
+        let geometry = tessellate_stroke(&points, &style);
+        // Two quads (8 vertices) plus one join triangle (3 vertices).
+        assert_eq!(geometry.vertices.len(), 11);
+    }
+
+    #[test]
+    fn round_join_emits_more_vertices_than_bevel() {
+        let points = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)];
+        let bevel = tessellate_stroke(&points, &style_with(LineJoin::Bevel, LineCap::Butt));
+        let round = tessellate_stroke(&points, &style_with(LineJoin::Round, LineCap::Butt));
+        assert!(round.vertices.len() > bevel.vertices.len());
+    }
+
+    #[test]
+    fn square_and_round_caps_differ() {
+        let points = [(0.0, 0.0), (1.0, 0.0)];
+        let square = tessellate_stroke(&points, &style_with(LineJoin::Miter, LineCap::Square));
+        let round = tessellate_stroke(&points, &style_with(LineJoin::Miter, LineCap::Round));
+        assert_ne!(square.vertices.len(), round.vertices.len());
+    }
+}